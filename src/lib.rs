@@ -0,0 +1,10 @@
+//! `romio` is an asynchronous I/O library built on top of `futures` and
+//! `mio`, providing non-blocking network primitives for `async`/`await`
+//! code.
+
+#![feature(async_await, await_macro, futures_api)]
+
+pub mod reactor;
+
+#[cfg(unix)]
+pub mod uds;
@@ -0,0 +1,626 @@
+//! The romio reactor drives `mio`-registered I/O resources and wakes the
+//! tasks that are waiting on them.
+
+mod background;
+
+pub use self::background::{Background, Shutdown};
+
+use futures::task::Waker;
+use futures::Poll;
+use lazy_static::lazy_static;
+use mio::{self, Evented};
+use slab::Slab;
+
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Token used to wake the reactor's `mio::Poll` out of a blocking `turn`.
+const WAKEUP_TOKEN: mio::Token = mio::Token(0);
+
+lazy_static! {
+    /// The default reactor, lazily started on a background thread the
+    /// first time any I/O resource is registered.
+    static ref DEFAULT: Handle = {
+        let reactor = Reactor::new().expect("failed to create reactor");
+        let handle = reactor.handle();
+        reactor
+            .background()
+            .expect("failed to spawn reactor thread")
+            .forget();
+        handle
+    };
+}
+
+/// The core I/O reactor, driving every resource registered against it.
+pub struct Reactor {
+    events: mio::Events,
+    inner: Arc<Inner>,
+}
+
+/// A handle to a [`Reactor`], used to register I/O resources with it.
+///
+/// [`Reactor`]: struct.Reactor.html
+#[derive(Clone)]
+pub struct Handle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    io: mio::Poll,
+    registry: Mutex<Slab<Arc<ScheduledIo>>>,
+    // Kept alive for as long as the reactor runs; dropping it would
+    // deregister the wakeup source from `io`.
+    wakeup_registration: mio::Registration,
+    wakeup_set_readiness: mio::SetReadiness,
+}
+
+/// Number of low bits of `ScheduledIo::readiness` occupied by the
+/// `mio::Ready` bitmask; the remaining high bits are a tick counter,
+/// bumped every time an event is applied, that lets a stale readiness
+/// snapshot taken before a `clear_*_ready` call be told apart from a
+/// fresh one.
+const READINESS_BITS: u32 = 8;
+const READINESS_MASK: usize = (1 << READINESS_BITS) - 1;
+
+/// Per-resource reactor state: the readiness bitmask/tick, and the
+/// tasks currently waiting on it.
+///
+/// Unlike a single waker slot, `read_waiters`/`write_waiters` allow any
+/// number of tasks to wait on the same direction concurrently — needed
+/// once a resource can be cloned or split (e.g. several `accept()`
+/// callers sharing one `UnixListener`, or the owned read/write halves
+/// of a `UnixStream`).
+struct ScheduledIo {
+    readiness: AtomicUsize,
+    read_waiters: Mutex<Slab<Waker>>,
+    write_waiters: Mutex<Slab<Waker>>,
+}
+
+/// Registers `lw` in `waiters`, returning the key it was stored under so
+/// it can be unregistered later (e.g. when the future waiting on it is
+/// dropped before being woken). Returns the key of an existing entry
+/// instead of inserting a duplicate if `lw` already has one.
+fn register_waiter(waiters: &Mutex<Slab<Waker>>, lw: &Waker) -> usize {
+    let mut waiters = waiters.lock().unwrap();
+
+    if let Some((key, _)) = waiters.iter().find(|(_, w)| w.will_wake(lw)) {
+        return key;
+    }
+
+    waiters.insert(lw.clone())
+}
+
+/// Removes a single waiter previously registered with `register_waiter`.
+/// A no-op if `key` was already woken and drained, so this is safe to
+/// call unconditionally from a `Drop` impl.
+fn remove_waiter(waiters: &Mutex<Slab<Waker>>, key: usize) {
+    waiters.lock().unwrap().try_remove(key);
+}
+
+fn wake_all(waiters: &Mutex<Slab<Waker>>) {
+    for waiter in waiters.lock().unwrap().drain() {
+        waiter.wake();
+    }
+}
+
+// ===== impl Reactor =====
+
+impl Reactor {
+    /// Creates a new reactor, ready to register I/O resources against.
+    pub fn new() -> io::Result<Reactor> {
+        let io = mio::Poll::new()?;
+        let (registration, set_readiness) = mio::Registration::new2();
+
+        io.register(
+            &registration,
+            WAKEUP_TOKEN,
+            mio::Ready::readable(),
+            mio::PollOpt::edge(),
+        )?;
+
+        // Reserve slab key 0 for the wakeup source itself: `Slab::insert`
+        // hands out key 0 first, which as a `mio::Token` would collide
+        // with `WAKEUP_TOKEN` and silently swallow that resource's events
+        // forever (`turn` skips token 0 unconditionally). This placeholder
+        // is never removed, so no real registration can ever land on it.
+        let mut registry = Slab::new();
+        let reserved = registry.insert(Arc::new(ScheduledIo::new()));
+        debug_assert_eq!(reserved, 0, "wakeup slot must reserve slab key 0");
+
+        let inner = Arc::new(Inner {
+            io,
+            registry: Mutex::new(registry),
+            wakeup_registration: registration,
+            wakeup_set_readiness: set_readiness,
+        });
+
+        Ok(Reactor {
+            events: mio::Events::with_capacity(1024),
+            inner,
+        })
+    }
+
+    /// Returns a handle to this reactor.
+    pub fn handle(&self) -> Handle {
+        Handle {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Runs this reactor on its own background thread, returning a handle
+    /// that can be used to shut it down.
+    pub fn background(self) -> io::Result<Background> {
+        Background::new(self)
+    }
+
+    /// Returns true if no I/O resources are currently registered.
+    pub fn is_idle(&self) -> bool {
+        // Slot 0 is permanently reserved for the wakeup source (see
+        // `Reactor::new`), so an otherwise-empty registry has a length of
+        // 1, not 0.
+        self.inner.registry.lock().unwrap().len() <= 1
+    }
+
+    /// Blocks the current thread for at most `timeout`, driving any ready
+    /// I/O resources and waking their tasks.
+    pub fn turn(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self.inner.io.poll(&mut self.events, timeout) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let registry = self.inner.registry.lock().unwrap();
+
+        for event in self.events.iter() {
+            let token = event.token();
+
+            if token == WAKEUP_TOKEN {
+                continue;
+            }
+
+            if let Some(io) = registry.get(token.0) {
+                io.set_readiness(event.readiness());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// ===== impl Handle =====
+
+impl Handle {
+    /// Returns a handle to the default, lazily started reactor.
+    pub(crate) fn current() -> Handle {
+        DEFAULT.clone()
+    }
+
+    /// Wakes the reactor thread out of a blocking `turn`.
+    pub(crate) fn wakeup(&self) {
+        let _ = self
+            .inner
+            .wakeup_set_readiness
+            .set_readiness(mio::Ready::readable());
+    }
+
+    fn register(&self, io: &dyn Evented) -> io::Result<(usize, Arc<ScheduledIo>)> {
+        let scheduled = Arc::new(ScheduledIo::new());
+
+        let mut registry = self.inner.registry.lock().unwrap();
+        let key = registry.insert(scheduled.clone());
+
+        if let Err(e) = self.inner.io_register(io, key) {
+            registry.remove(key);
+            return Err(e);
+        }
+
+        Ok((key, scheduled))
+    }
+
+    fn deregister(&self, io: &dyn Evented, key: usize) -> io::Result<()> {
+        self.inner.io_deregister(io)?;
+        self.inner.registry.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+impl Inner {
+    fn io_register(&self, io: &dyn Evented, key: usize) -> io::Result<()> {
+        self.io.register(
+            io,
+            mio::Token(key),
+            mio::Ready::readable() | mio::Ready::writable(),
+            mio::PollOpt::edge(),
+        )
+    }
+
+    fn io_deregister(&self, io: &dyn Evented) -> io::Result<()> {
+        self.io.deregister(io)
+    }
+}
+
+impl ScheduledIo {
+    fn new() -> ScheduledIo {
+        ScheduledIo {
+            readiness: AtomicUsize::new(0),
+            read_waiters: Mutex::new(Slab::new()),
+            write_waiters: Mutex::new(Slab::new()),
+        }
+    }
+
+    /// ORs a newly observed readiness into the bitmask and bumps the
+    /// tick, so readiness is never lost between polls even if this
+    /// resource currently has no waiters.
+    fn set_readiness(&self, ready: mio::Ready) {
+        let mut curr = self.readiness.load(SeqCst);
+
+        loop {
+            let tick = (curr >> READINESS_BITS) + 1;
+            let next = (tick << READINESS_BITS) | ((curr & READINESS_MASK) | ready.as_usize());
+
+            match self
+                .readiness
+                .compare_exchange_weak(curr, next, SeqCst, SeqCst)
+            {
+                Ok(_) => break,
+                Err(actual) => curr = actual,
+            }
+        }
+
+        if ready.is_readable() {
+            wake_all(&self.read_waiters);
+        }
+
+        if ready.is_writable() {
+            wake_all(&self.write_waiters);
+        }
+    }
+
+    fn readiness(&self, mask: mio::Ready) -> mio::Ready {
+        mio::Ready::from_usize(self.readiness.load(SeqCst) & READINESS_MASK) & mask
+    }
+
+    /// Like `readiness`, but also returns the tick it was observed under,
+    /// so a later `clear_readiness_since` call can tell whether a fresh
+    /// event has landed in the meantime instead of re-deriving a baseline
+    /// from whatever it finds loaded at that later point.
+    fn readiness_and_tick(&self, mask: mio::Ready) -> (mio::Ready, usize) {
+        let curr = self.readiness.load(SeqCst);
+        (
+            mio::Ready::from_usize(curr & READINESS_MASK) & mask,
+            curr >> READINESS_BITS,
+        )
+    }
+
+    /// Clears `mask` out of the readiness bitmask, but only if the tick is
+    /// still `since` — i.e. only if no `set_readiness` has run since
+    /// `since` was observed. If the tick has moved on, a fresh event may
+    /// already have been reported to (and missed by) whoever observed
+    /// `since`, so the bits and the tick that records them are left
+    /// untouched rather than clearing away readiness nobody actually saw.
+    fn clear_readiness_since(&self, mask: mio::Ready, since: usize) {
+        let mut curr = self.readiness.load(SeqCst);
+
+        loop {
+            if (curr >> READINESS_BITS) != since {
+                break;
+            }
+
+            let next = curr & !mask.as_usize();
+            if next == curr {
+                break;
+            }
+
+            match self
+                .readiness
+                .compare_exchange_weak(curr, next, SeqCst, SeqCst)
+            {
+                Ok(_) => break,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+}
+
+// ===== impl PollEvented =====
+
+/// Associates an I/O resource with the reactor, tracking its read and
+/// write readiness.
+pub struct PollEvented<E: Evented> {
+    io: E,
+    handle: Handle,
+    key: usize,
+    scheduled: Arc<ScheduledIo>,
+}
+
+impl<E: Evented> PollEvented<E> {
+    /// Registers `io` with the default reactor.
+    pub fn new(io: E) -> PollEvented<E> {
+        let handle = Handle::current();
+        let (key, scheduled) = handle
+            .register(&io)
+            .expect("failed to register I/O resource with the reactor");
+
+        PollEvented {
+            io,
+            handle,
+            key,
+            scheduled,
+        }
+    }
+
+    /// Returns a reference to the wrapped I/O resource.
+    pub fn get_ref(&self) -> &E {
+        &self.io
+    }
+
+    /// Returns a mutable reference to the wrapped I/O resource.
+    pub fn get_mut(&mut self) -> &mut E {
+        &mut self.io
+    }
+
+    /// Polls for read readiness.
+    ///
+    /// If the resource isn't ready, `lw` is added to the set of waiters
+    /// woken the next time read readiness is observed; any number of
+    /// tasks may be waiting at once.
+    pub fn poll_read_ready(&self, lw: &Waker) -> Poll<io::Result<mio::Ready>> {
+        match self.poll_read_ready_tick(lw) {
+            Poll::Ready(result) => Poll::Ready(result.map(|(ready, _)| ready)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Polls for write readiness. See [`poll_read_ready`](#method.poll_read_ready).
+    pub fn poll_write_ready(&self, lw: &Waker) -> Poll<io::Result<mio::Ready>> {
+        match self.poll_write_ready_tick(lw) {
+            Poll::Ready(result) => Poll::Ready(result.map(|(ready, _)| ready)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Like `poll_read_ready`, but also returns the tick the readiness was
+    /// observed under, for callers that need to pass it to
+    /// `clear_read_ready` so a racing `set_readiness` can be detected
+    /// instead of silently cleared away.
+    pub(crate) fn poll_read_ready_tick(&self, lw: &Waker) -> Poll<io::Result<(mio::Ready, usize)>> {
+        let (ready, tick) = self.scheduled.readiness_and_tick(mio::Ready::readable());
+
+        if ready.is_empty() {
+            let _ = register_waiter(&self.scheduled.read_waiters, lw);
+
+            // Re-check after registering: a readiness event could have
+            // landed between the check above and the registration.
+            let (ready, tick) = self.scheduled.readiness_and_tick(mio::Ready::readable());
+            if ready.is_empty() {
+                return Poll::Pending;
+            }
+            return Poll::Ready(Ok((ready, tick)));
+        }
+
+        Poll::Ready(Ok((ready, tick)))
+    }
+
+    /// Like `poll_write_ready`, but also returns the observed tick. See
+    /// [`poll_read_ready_tick`](#method.poll_read_ready_tick).
+    pub(crate) fn poll_write_ready_tick(&self, lw: &Waker) -> Poll<io::Result<(mio::Ready, usize)>> {
+        let (ready, tick) = self.scheduled.readiness_and_tick(mio::Ready::writable());
+
+        if ready.is_empty() {
+            let _ = register_waiter(&self.scheduled.write_waiters, lw);
+
+            let (ready, tick) = self.scheduled.readiness_and_tick(mio::Ready::writable());
+            if ready.is_empty() {
+                return Poll::Pending;
+            }
+            return Poll::Ready(Ok((ready, tick)));
+        }
+
+        Poll::Ready(Ok((ready, tick)))
+    }
+
+    /// Clears the cached read readiness, so the next `poll_read_ready`
+    /// waits for a fresh readiness event.
+    ///
+    /// `tick` must be the tick observed alongside the `Ready` that made
+    /// the caller's I/O attempt look worth trying (from
+    /// `poll_read_ready_tick`). Registers `lw` before clearing and
+    /// rechecks readiness afterward, so a `set_readiness` racing with this
+    /// call can't both fire `wake_all` before `lw` is in the waiter list
+    /// *and* have its bit masked back off by the clear that follows:
+    /// either `lw` sees the race and gets woken here, or
+    /// `clear_readiness_since` notices the tick moved past `tick` and
+    /// leaves the fresh bit (and wakeup) alone.
+    pub(crate) fn clear_read_ready(&self, tick: usize, lw: &Waker) -> io::Result<()> {
+        register_waiter(&self.scheduled.read_waiters, lw);
+        self.scheduled
+            .clear_readiness_since(mio::Ready::readable(), tick);
+
+        if !self.scheduled.readiness(mio::Ready::readable()).is_empty() {
+            wake_all(&self.scheduled.read_waiters);
+        }
+
+        Ok(())
+    }
+
+    /// Clears the cached write readiness. See
+    /// [`clear_read_ready`](#method.clear_read_ready).
+    pub(crate) fn clear_write_ready(&self, tick: usize, lw: &Waker) -> io::Result<()> {
+        register_waiter(&self.scheduled.write_waiters, lw);
+        self.scheduled
+            .clear_readiness_since(mio::Ready::writable(), tick);
+
+        if !self.scheduled.readiness(mio::Ready::writable()).is_empty() {
+            wake_all(&self.scheduled.write_waiters);
+        }
+
+        Ok(())
+    }
+
+    /// Clears the cached read readiness without registering a waiter, if
+    /// the tick is still `tick` (the one observed when `read_ready()`
+    /// last resolved).
+    ///
+    /// Used by the guarded [`read_ready`](#method.read_ready) retry loop,
+    /// which re-registers itself on its next poll rather than here.
+    pub(crate) fn reset_read_ready(&self, tick: usize) {
+        self.scheduled
+            .clear_readiness_since(mio::Ready::readable(), tick);
+    }
+
+    /// Clears the cached write readiness without registering a waiter, if
+    /// the tick is still `tick`. See
+    /// [`reset_read_ready`](#method.reset_read_ready).
+    pub(crate) fn reset_write_ready(&self, tick: usize) {
+        self.scheduled
+            .clear_readiness_since(mio::Ready::writable(), tick);
+    }
+
+    /// Returns a future that resolves once this resource is read-ready.
+    ///
+    /// Unlike `poll_read_ready`, the returned future unregisters its
+    /// waiter if dropped before resolving (e.g. it loses a `select!` race
+    /// against another future), so cancelling it doesn't leave a stale
+    /// waker behind.
+    pub fn read_ready(&self) -> Readiness<'_, E> {
+        Readiness {
+            io: self,
+            mask: mio::Ready::readable(),
+            key: None,
+        }
+    }
+
+    /// Returns a future that resolves once this resource is write-ready.
+    /// See [`read_ready`](#method.read_ready).
+    pub fn write_ready(&self) -> Readiness<'_, E> {
+        Readiness {
+            io: self,
+            mask: mio::Ready::writable(),
+            key: None,
+        }
+    }
+
+    /// Deregisters the inner I/O resource from the reactor and hands it
+    /// back, consuming this `PollEvented`.
+    ///
+    /// Used to implement `IntoRawFd` for resources adopted from elsewhere,
+    /// where ownership must leave the reactor cleanly instead of being
+    /// dropped.
+    pub fn into_inner(self) -> io::Result<E> {
+        let this = mem::ManuallyDrop::new(self);
+
+        // Safety: `this` is never accessed again, so each field is read
+        // out of it at most once here. Wrapping `self` in `ManuallyDrop`
+        // suppressed the destructor that would otherwise deregister `io`
+        // a second time.
+        let (io, handle, key, scheduled) = unsafe {
+            (
+                ptr::read(&this.io),
+                ptr::read(&this.handle),
+                this.key,
+                ptr::read(&this.scheduled),
+            )
+        };
+        drop(scheduled);
+
+        let result = handle.deregister(&io, key);
+        drop(handle);
+
+        match result {
+            Ok(()) => Ok(io),
+            Err(e) => {
+                // Give up on `io` too rather than returning it in an
+                // unregistered-but-maybe-still-open state the caller can't
+                // reason about.
+                drop(io);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A future, returned by [`PollEvented::read_ready`]/[`write_ready`], that
+/// resolves once its resource becomes ready in the direction it was
+/// created for.
+///
+/// [`PollEvented::read_ready`]: struct.PollEvented.html#method.read_ready
+/// [`write_ready`]: struct.PollEvented.html#method.write_ready
+pub struct Readiness<'a, E: Evented> {
+    io: &'a PollEvented<E>,
+    mask: mio::Ready,
+    key: Option<usize>,
+}
+
+impl<'a, E: Evented> Future for Readiness<'a, E> {
+    /// The observed readiness, paired with the tick it was observed
+    /// under. Callers that retry their I/O and fall back to
+    /// `reset_read_ready`/`reset_write_ready` must pass this tick through,
+    /// so a `set_readiness` racing with the retry is detected rather than
+    /// silently cleared away.
+    type Output = io::Result<(mio::Ready, usize)>;
+
+    fn poll(self: Pin<&mut Self>, lw: &Waker) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let waiters = if this.mask.is_writable() {
+            &this.io.scheduled.write_waiters
+        } else {
+            &this.io.scheduled.read_waiters
+        };
+
+        let (ready, tick) = this.io.scheduled.readiness_and_tick(this.mask);
+        if !ready.is_empty() {
+            if let Some(key) = this.key.take() {
+                remove_waiter(waiters, key);
+            }
+            return Poll::Ready(Ok((ready, tick)));
+        }
+
+        // Drop any previous registration before re-registering: the waker
+        // passed on this poll may differ from the last one (e.g. the task
+        // moved between executor threads), and `register_waiter`'s dedup
+        // only catches the current call's waker, not a stale one already
+        // in the slab under a different key.
+        if let Some(key) = this.key.take() {
+            remove_waiter(waiters, key);
+        }
+        this.key = Some(register_waiter(waiters, lw));
+
+        // Re-check after registering, same as poll_read_ready/poll_write_ready.
+        let (ready, tick) = this.io.scheduled.readiness_and_tick(this.mask);
+        if ready.is_empty() {
+            Poll::Pending
+        } else {
+            if let Some(key) = this.key.take() {
+                remove_waiter(waiters, key);
+            }
+            Poll::Ready(Ok((ready, tick)))
+        }
+    }
+}
+
+impl<'a, E: Evented> Drop for Readiness<'a, E> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let waiters = if self.mask.is_writable() {
+                &self.io.scheduled.write_waiters
+            } else {
+                &self.io.scheduled.read_waiters
+            };
+            remove_waiter(waiters, key);
+        }
+    }
+}
+
+impl<E: Evented> Drop for PollEvented<E> {
+    fn drop(&mut self) {
+        let _ = self.handle.deregister(&self.io, self.key);
+    }
+}
@@ -0,0 +1,113 @@
+//! Low-level `sendmsg`/`recvmsg` helpers for passing open file descriptors
+//! between processes over a Unix domain socket via `SCM_RIGHTS`.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+
+/// The most file descriptors a single `recv_fds` call will extract from
+/// one control message; callers wanting more should increase this.
+const MAX_FDS: usize = 32;
+
+/// Sends `buf` on `sock`, attaching `fds` as an `SCM_RIGHTS` ancillary
+/// message so the receiving process gets its own copies of the
+/// descriptors.
+pub(crate) fn send_fds<T: AsRawFd>(sock: &T, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let mut cmsg_space = vec![0u8; unsafe { libc::CMSG_SPACE(cmsg_len(fds.len())) as usize }];
+
+    if !fds.is_empty() {
+        msg.msg_control = cmsg_space.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = cmsg_space.len() as _;
+
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(cmsg_len(fds.len())) as _;
+
+            ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+        }
+    }
+
+    let ret = unsafe { libc::sendmsg(sock.as_raw_fd(), &msg, 0) };
+
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// Receives into `buf` from `sock`, appending any file descriptors carried
+/// in an `SCM_RIGHTS` ancillary message to `fds`.
+///
+/// Returns an `Other` error if the kernel reports the control message was
+/// truncated (`MSG_CTRUNC`), since that means some of the sent
+/// descriptors were silently dropped rather than duplicated into this
+/// process.
+pub(crate) fn recv_fds<T: AsRawFd>(
+    sock: &T,
+    buf: &mut [u8],
+    fds: &mut Vec<RawFd>,
+) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut cmsg_space = vec![0u8; unsafe { libc::CMSG_SPACE(cmsg_len(MAX_FDS)) as usize }];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_space.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_space.len() as _;
+
+    let ret = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "SCM_RIGHTS control message was truncated; a file descriptor may have been dropped",
+        ));
+    }
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let count = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize)
+                    / mem::size_of::<RawFd>();
+                let count = count.min(MAX_FDS);
+
+                for i in 0..count {
+                    fds.push(ptr::read_unaligned(data.add(i)));
+                }
+            }
+
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    Ok(ret as usize)
+}
+
+fn cmsg_len(fd_count: usize) -> libc::c_uint {
+    (fd_count * mem::size_of::<RawFd>()) as libc::c_uint
+}
@@ -1,4 +1,5 @@
 use crate::reactor::PollEvented;
+use crate::uds::ancillary;
 
 use futures::task::Waker;
 use futures::{ready, Poll};
@@ -8,9 +9,10 @@ use mio_uds;
 use std::fmt;
 use std::io;
 use std::net::Shutdown;
-use std::os::unix::io::{AsRawFd, RawFd};
-use std::os::unix::net::SocketAddr;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
 use std::path::Path;
+use std::sync::Arc;
 
 /// An I/O object representing a Unix datagram socket.
 pub struct UnixDatagram {
@@ -78,6 +80,37 @@ impl UnixDatagram {
         Ok(UnixDatagram::new(socket))
     }
 
+    /// Adopts a `std::os::unix::net::UnixDatagram` into the reactor.
+    ///
+    /// This is useful for sockets created outside of romio, for example
+    /// via systemd socket activation or inherited from a parent process,
+    /// that should be driven by the reactor from now on.
+    pub fn from_std(socket: net::UnixDatagram) -> io::Result<UnixDatagram> {
+        socket.set_nonblocking(true)?;
+        let socket = mio_uds::UnixDatagram::from_datagram(socket)?;
+        Ok(UnixDatagram::new(socket))
+    }
+
+    /// Creates a `UnixDatagram` and connects it to the specified path.
+    ///
+    /// Once connected, `send`/`recv` can be used in place of
+    /// `send_to`/`recv_from`, since the socket remembers its peer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::connect("/tmp/sock")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<UnixDatagram> {
+        let socket = mio_uds::UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(UnixDatagram::new(socket))
+    }
+
     /// Test whether this socket is ready to be read or not.
     pub fn poll_read_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
         self.io.poll_read_ready(lw)
@@ -130,12 +163,82 @@ impl UnixDatagram {
         lw: &Waker,
         buf: &mut [u8],
     ) -> Poll<io::Result<(usize, SocketAddr)>> {
-        ready!(self.io.poll_read_ready(lw)?);
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
 
         let r = self.io.get_ref().recv_from(buf);
 
         if is_wouldblock(&r) {
-            self.io.clear_read_ready(lw)?;
+            self.io.clear_read_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Receives data from the socket, returning the number of bytes read
+    /// and the address it came from.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::bind("/tmp/sock")?;
+    /// let mut buf = vec![0; 1024];
+    /// let (n, addr) = await!(sock.recv_from(&mut buf))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        loop {
+            let (_, tick) = await!(self.io.read_ready())?;
+
+            let r = self.io.get_ref().recv_from(buf);
+            if is_wouldblock(&r) {
+                self.io.reset_read_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Receives data from this socket's connected peer.
+    ///
+    /// The socket must have been connected with [`connect`](#method.connect).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::connect("/tmp/sock")?;
+    /// let mut buf = vec![0; 1024];
+    /// let n = await!(sock.recv(&mut buf))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.read_ready())?;
+
+            let r = self.io.get_ref().recv(buf);
+            if is_wouldblock(&r) {
+                self.io.reset_read_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Attempts to receive data from this socket's connected peer without
+    /// blocking.
+    pub fn poll_recv(&self, lw: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
+
+        let r = self.io.get_ref().recv(buf);
+
+        if is_wouldblock(&r) {
+            self.io.clear_read_ready(tick, lw)?;
             Poll::Pending
         } else {
             Poll::Ready(r)
@@ -151,18 +254,192 @@ impl UnixDatagram {
         buf: &[u8],
         path: impl AsRef<Path>,
     ) -> Poll<io::Result<usize>> {
-        ready!(self.io.poll_write_ready(lw)?);
+        let (_, tick) = ready!(self.io.poll_write_ready_tick(lw)?);
 
         let r = self.io.get_ref().send_to(buf, path);
 
         if is_wouldblock(&r) {
-            self.io.clear_write_ready(lw)?;
+            self.io.clear_write_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Sends data on the socket to the specified address.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::unbound()?;
+    /// await!(sock.send_to(b"hello", "/tmp/sock"))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn send_to(&self, buf: &[u8], path: impl AsRef<Path>) -> io::Result<usize> {
+        let path = path.as_ref();
+
+        loop {
+            let (_, tick) = await!(self.io.write_ready())?;
+
+            let r = self.io.get_ref().send_to(buf, path);
+            if is_wouldblock(&r) {
+                self.io.reset_write_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Sends data on the socket to its connected peer.
+    ///
+    /// The socket must have been connected with [`connect`](#method.connect).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::connect("/tmp/sock")?;
+    /// await!(sock.send(b"hello"))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.write_ready())?;
+
+            let r = self.io.get_ref().send(buf);
+            if is_wouldblock(&r) {
+                self.io.reset_write_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Attempts to send data to this socket's connected peer without
+    /// blocking.
+    pub fn poll_send(&self, lw: &Waker, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_write_ready_tick(lw)?);
+
+        let r = self.io.get_ref().send(buf);
+
+        if is_wouldblock(&r) {
+            self.io.clear_write_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Attempts to send `buf` to this socket's connected peer along with
+    /// `fds` as `SCM_RIGHTS` ancillary data, without blocking.
+    pub fn poll_send_with_fds(
+        &self,
+        lw: &Waker,
+        buf: &[u8],
+        fds: &[RawFd],
+    ) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_write_ready_tick(lw)?);
+
+        let r = ancillary::send_fds(self.io.get_ref(), buf, fds);
+
+        if is_wouldblock(&r) {
+            self.io.clear_write_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Sends `buf` to this socket's connected peer, handing it its own
+    /// duplicate of each descriptor in `fds` via an `SCM_RIGHTS`
+    /// ancillary message.
+    ///
+    /// The socket must have been connected with [`connect`](#method.connect).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// # async fn run(file: std::fs::File) -> std::io::Result<()> {
+    /// let sock = UnixDatagram::connect("/tmp/sock")?;
+    /// await!(sock.send_with_fds(b"here's a file", &[file.as_raw_fd()]))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn send_with_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.write_ready())?;
+
+            let r = ancillary::send_fds(self.io.get_ref(), buf, fds);
+            if is_wouldblock(&r) {
+                self.io.reset_write_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Attempts to receive into `buf` from this socket's connected peer,
+    /// along with any file descriptors sent alongside it, without
+    /// blocking.
+    ///
+    /// Descriptors found in the `SCM_RIGHTS` control message, if any, are
+    /// appended to `fds`.
+    pub fn poll_recv_with_fds(
+        &self,
+        lw: &Waker,
+        buf: &mut [u8],
+        fds: &mut Vec<RawFd>,
+    ) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
+
+        let r = ancillary::recv_fds(self.io.get_ref(), buf, fds);
+
+        if is_wouldblock(&r) {
+            self.io.clear_read_ready(tick, lw)?;
             Poll::Pending
         } else {
             Poll::Ready(r)
         }
     }
 
+    /// Receives into `buf` from this socket's connected peer, appending
+    /// any file descriptors sent alongside it as `SCM_RIGHTS` ancillary
+    /// data to `fds`.
+    ///
+    /// The socket must have been connected with [`connect`](#method.connect).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::connect("/tmp/sock")?;
+    /// let mut buf = vec![0; 1024];
+    /// let mut fds = Vec::new();
+    /// let n = await!(sock.recv_with_fds(&mut buf, &mut fds))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_with_fds(&self, buf: &mut [u8], fds: &mut Vec<RawFd>) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.read_ready())?;
+
+            let r = ancillary::recv_fds(self.io.get_ref(), buf, fds);
+            if is_wouldblock(&r) {
+                self.io.reset_read_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
     /// Returns the value of the `SO_ERROR` option.
     ///
     /// # Examples
@@ -201,6 +478,30 @@ impl UnixDatagram {
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         self.io.get_ref().shutdown(how)
     }
+
+    /// Splits this datagram socket into owned receive and send halves
+    /// that can be moved into separate tasks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixDatagram;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let sock = UnixDatagram::unbound()?;
+    /// let (recv_half, send_half) = sock.into_split();
+    /// # Ok(()) }
+    /// ```
+    pub fn into_split(self) -> (OwnedRecvHalf, OwnedSendHalf) {
+        let inner = Arc::new(self);
+
+        (
+            OwnedRecvHalf {
+                inner: inner.clone(),
+            },
+            OwnedSendHalf { inner },
+        )
+    }
 }
 
 impl fmt::Debug for UnixDatagram {
@@ -215,9 +516,133 @@ impl AsRawFd for UnixDatagram {
     }
 }
 
+impl FromRawFd for UnixDatagram {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixDatagram {
+        UnixDatagram::from_std(net::UnixDatagram::from_raw_fd(fd))
+            .expect("failed to register adopted fd with the reactor")
+    }
+}
+
+impl IntoRawFd for UnixDatagram {
+    fn into_raw_fd(self) -> RawFd {
+        self.io
+            .into_inner()
+            .expect("failed to deregister socket from the reactor")
+            .into_raw_fd()
+    }
+}
+
 fn is_wouldblock<T>(r: &io::Result<T>) -> bool {
     match *r {
         Ok(_) => false,
         Err(ref e) => e.kind() == io::ErrorKind::WouldBlock,
     }
 }
+
+/// Owned receive half of a [`UnixDatagram`], created by
+/// [`UnixDatagram::into_split`].
+///
+/// [`UnixDatagram`]: struct.UnixDatagram.html
+/// [`UnixDatagram::into_split`]: struct.UnixDatagram.html#method.into_split
+#[derive(Debug)]
+pub struct OwnedRecvHalf {
+    inner: Arc<UnixDatagram>,
+}
+
+/// Owned send half of a [`UnixDatagram`], created by
+/// [`UnixDatagram::into_split`].
+///
+/// [`UnixDatagram`]: struct.UnixDatagram.html
+/// [`UnixDatagram::into_split`]: struct.UnixDatagram.html#method.into_split
+#[derive(Debug)]
+pub struct OwnedSendHalf {
+    inner: Arc<UnixDatagram>,
+}
+
+/// Error returned when reuniting two halves that did not originate from
+/// the same `UnixDatagram`.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedRecvHalf, pub OwnedSendHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tried to reunite two halves that are not from the same socket"
+        )
+    }
+}
+
+impl std::error::Error for ReuniteError {}
+
+fn reunite(recv: OwnedRecvHalf, send: OwnedSendHalf) -> Result<UnixDatagram, ReuniteError> {
+    if Arc::ptr_eq(&recv.inner, &send.inner) {
+        drop(send);
+        Ok(Arc::try_unwrap(recv.inner).expect("UnixDatagram: more than one strong reference"))
+    } else {
+        Err(ReuniteError(recv, send))
+    }
+}
+
+impl OwnedRecvHalf {
+    /// Attempts to put the two halves of a `UnixDatagram` back together
+    /// and recover the original socket. Succeeds only if the two halves
+    /// originated from the same call to [`UnixDatagram::into_split`].
+    ///
+    /// [`UnixDatagram::into_split`]: struct.UnixDatagram.html#method.into_split
+    pub fn reunite(self, other: OwnedSendHalf) -> Result<UnixDatagram, ReuniteError> {
+        reunite(self, other)
+    }
+
+    /// Receives data from the socket.
+    pub fn poll_recv_from(
+        &self,
+        lw: &Waker,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.inner.poll_recv_from(lw, buf)
+    }
+
+    /// Receives data from this socket's connected peer.
+    pub fn poll_recv(&self, lw: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.inner.poll_recv(lw, buf)
+    }
+}
+
+impl OwnedSendHalf {
+    /// Attempts to put the two halves of a `UnixDatagram` back together
+    /// and recover the original socket. Succeeds only if the two halves
+    /// originated from the same call to [`UnixDatagram::into_split`].
+    ///
+    /// [`UnixDatagram::into_split`]: struct.UnixDatagram.html#method.into_split
+    pub fn reunite(self, other: OwnedRecvHalf) -> Result<UnixDatagram, ReuniteError> {
+        reunite(other, self)
+    }
+
+    /// Sends data on the socket to the specified address.
+    pub fn poll_send_to(
+        &self,
+        lw: &Waker,
+        buf: &[u8],
+        path: impl AsRef<Path>,
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_send_to(lw, buf, path)
+    }
+
+    /// Sends data on the socket to its connected peer.
+    pub fn poll_send(&self, lw: &Waker, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.inner.poll_send(lw, buf)
+    }
+}
+
+impl AsRawFd for OwnedRecvHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsRawFd for OwnedSendHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
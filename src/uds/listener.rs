@@ -0,0 +1,178 @@
+use crate::reactor::PollEvented;
+use crate::uds::UnixStream;
+
+use futures::task::Waker;
+use futures::{ready, Poll, Stream};
+use mio::Ready;
+use mio_uds;
+
+use std::fmt;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A Unix domain socket server, listening for connections.
+///
+/// Cloning a `UnixListener` is cheap: clones share the same underlying
+/// socket, so several tasks can each call [`accept`](#method.accept) on
+/// their own clone to fan incoming connections across a worker pool. The
+/// socket is only closed once the last clone is dropped.
+#[derive(Clone)]
+pub struct UnixListener {
+    io: Arc<PollEvented<mio_uds::UnixListener>>,
+}
+
+impl UnixListener {
+    /// Creates a new `UnixListener` bound to the specified path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixListener;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let listener = UnixListener::bind("/tmp/sock")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+        let listener = mio_uds::UnixListener::bind(path)?;
+        let io = Arc::new(PollEvented::new(listener));
+        Ok(UnixListener { io })
+    }
+
+    /// Adopts a `std::os::unix::net::UnixListener` into the reactor.
+    ///
+    /// This is useful for listeners created outside of romio, for
+    /// example via systemd socket activation or inherited from a parent
+    /// process, that should be driven by the reactor from now on.
+    pub fn from_std(listener: net::UnixListener) -> io::Result<UnixListener> {
+        listener.set_nonblocking(true)?;
+        let listener = mio_uds::UnixListener::from_listener(listener)?;
+        let io = Arc::new(PollEvented::new(listener));
+        Ok(UnixListener { io })
+    }
+
+    /// Test whether this socket is ready to accept a connection or not.
+    pub fn poll_read_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
+        self.io.poll_read_ready(lw)
+    }
+
+    /// Attempts to accept a connection without blocking.
+    pub fn poll_accept(&self, lw: &Waker) -> Poll<io::Result<(UnixStream, SocketAddr)>> {
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
+
+        match self.io.get_ref().accept() {
+            Ok(Some((stream, addr))) => Poll::Ready(Ok((UnixStream::new(stream), addr))),
+            Ok(None) => {
+                self.io.clear_read_ready(tick, lw)?;
+                Poll::Pending
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.io.clear_read_ready(tick, lw)?;
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Accepts a new incoming connection on this listener.
+    ///
+    /// The returned future borrows only the handle it was called on, so
+    /// several clones of the same `UnixListener` can each call this
+    /// concurrently, letting a pool of worker tasks share one listening
+    /// socket.
+    pub async fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        loop {
+            let (_, tick) = await!(self.io.read_ready())?;
+
+            match self.io.get_ref().accept() {
+                Ok(Some((stream, addr))) => return Ok((UnixStream::new(stream), addr)),
+                Ok(None) => self.io.reset_read_ready(tick),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.io.reset_read_ready(tick)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns a stream over the connections being received on this
+    /// listener.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixListener;
+    /// use futures::StreamExt;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let listener = UnixListener::bind("/tmp/sock")?;
+    /// let mut incoming = listener.incoming();
+    /// while let Some(stream) = await!(incoming.next()) {
+    ///     let _stream = stream?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self }
+    }
+
+    /// Returns the local socket address of this listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().local_addr()
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.io.get_ref().take_error()
+    }
+}
+
+impl fmt::Debug for UnixListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.io.get_ref().fmt(f)
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
+        UnixListener::from_std(net::UnixListener::from_raw_fd(fd))
+            .expect("failed to register adopted fd with the reactor")
+    }
+}
+
+impl IntoRawFd for UnixListener {
+    fn into_raw_fd(self) -> RawFd {
+        Arc::try_unwrap(self.io)
+            .unwrap_or_else(|_| panic!("`into_raw_fd` called on a cloned `UnixListener`"))
+            .into_inner()
+            .expect("failed to deregister socket from the reactor")
+            .into_raw_fd()
+    }
+}
+
+/// A stream of connections accepted from a [`UnixListener`].
+///
+/// [`UnixListener`]: struct.UnixListener.html
+#[derive(Debug)]
+pub struct Incoming<'a> {
+    listener: &'a UnixListener,
+}
+
+impl<'a> Stream for Incoming<'a> {
+    type Item = io::Result<UnixStream>;
+
+    fn poll_next(self: Pin<&mut Self>, lw: &Waker) -> Poll<Option<Self::Item>> {
+        let (stream, _addr) = ready!(self.listener.poll_accept(lw)?);
+        Poll::Ready(Some(Ok(stream)))
+    }
+}
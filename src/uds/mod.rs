@@ -0,0 +1,13 @@
+//! Unix domain socket bindings, analogous to `std::os::unix::net`.
+
+pub mod datagram;
+pub mod stream;
+
+mod ancillary;
+mod listener;
+mod ucred;
+
+pub use self::datagram::UnixDatagram;
+pub use self::listener::{Incoming, UnixListener};
+pub use self::stream::UnixStream;
+pub use self::ucred::UCred;
@@ -0,0 +1,469 @@
+use crate::reactor::PollEvented;
+use crate::uds::ancillary;
+use crate::uds::ucred::{self, UCred};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::task::Waker;
+use futures::{ready, Poll};
+use mio::Ready;
+use mio_uds;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A structure representing a connected Unix socket.
+pub struct UnixStream {
+    io: PollEvented<mio_uds::UnixStream>,
+}
+
+impl UnixStream {
+    /// Connects to the socket named by `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let stream = await!(UnixStream::connect("/tmp/sock"))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn connect(path: impl AsRef<Path>) -> io::Result<UnixStream> {
+        let stream = mio_uds::UnixStream::connect(path)?;
+        let stream = UnixStream::new(stream);
+
+        await!(stream.io.write_ready())?;
+
+        if let Some(e) = stream.take_error()? {
+            return Err(e);
+        }
+
+        Ok(stream)
+    }
+
+    /// Creates an unnamed pair of connected sockets.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let (a, b) = UnixStream::pair()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = mio_uds::UnixStream::pair()?;
+        let a = UnixStream::new(a);
+        let b = UnixStream::new(b);
+
+        Ok((a, b))
+    }
+
+    pub(crate) fn new(stream: mio_uds::UnixStream) -> UnixStream {
+        let io = PollEvented::new(stream);
+        UnixStream { io }
+    }
+
+    /// Adopts a `std::os::unix::net::UnixStream` into the reactor.
+    ///
+    /// This is useful for sockets created outside of romio, for example
+    /// via systemd socket activation or inherited from a parent process,
+    /// that should be driven by the reactor from now on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    /// use std::os::unix::net::UnixStream as StdUnixStream;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let std_stream = StdUnixStream::connect("/tmp/sock")?;
+    /// let stream = UnixStream::from_std(std_stream)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn from_std(stream: net::UnixStream) -> io::Result<UnixStream> {
+        stream.set_nonblocking(true)?;
+        let stream = mio_uds::UnixStream::from_stream(stream)?;
+        Ok(UnixStream::new(stream))
+    }
+
+    /// Test whether this socket is ready to be read or not.
+    pub fn poll_read_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
+        self.io.poll_read_ready(lw)
+    }
+
+    /// Test whether this socket is ready to be written to or not.
+    pub fn poll_write_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
+        self.io.poll_write_ready(lw)
+    }
+
+    /// Attempts to read from the socket into `buf` without blocking.
+    pub fn poll_read(&self, lw: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
+
+        let r = (&*self.io.get_ref()).read(buf);
+
+        if is_wouldblock(&r) {
+            self.io.clear_read_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Attempts to write `buf` to the socket without blocking.
+    pub fn poll_write(&self, lw: &Waker, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_write_ready_tick(lw)?);
+
+        let r = (&*self.io.get_ref()).write(buf);
+
+        if is_wouldblock(&r) {
+            self.io.clear_write_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Attempts to send `buf` to the peer along with `fds` as `SCM_RIGHTS`
+    /// ancillary data, without blocking.
+    pub fn poll_send_with_fds(
+        &self,
+        lw: &Waker,
+        buf: &[u8],
+        fds: &[RawFd],
+    ) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_write_ready_tick(lw)?);
+
+        let r = ancillary::send_fds(self.io.get_ref(), buf, fds);
+
+        if is_wouldblock(&r) {
+            self.io.clear_write_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Sends `buf` to the peer, handing it its own duplicate of each
+    /// descriptor in `fds` via an `SCM_RIGHTS` ancillary message.
+    ///
+    /// This is how privilege-separated daemons and other fd-brokering
+    /// processes pass open files, sockets, or other descriptors across a
+    /// Unix domain socket, something a plain byte stream cannot do.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// # async fn run(file: std::fs::File) -> std::io::Result<()> {
+    /// let stream = await!(UnixStream::connect("/tmp/sock"))?;
+    /// await!(stream.send_with_fds(b"here's a file", &[file.as_raw_fd()]))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn send_with_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.write_ready())?;
+
+            let r = ancillary::send_fds(self.io.get_ref(), buf, fds);
+            if is_wouldblock(&r) {
+                self.io.reset_write_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Attempts to receive into `buf`, along with any file descriptors
+    /// sent alongside it, without blocking.
+    ///
+    /// Descriptors found in the `SCM_RIGHTS` control message, if any, are
+    /// appended to `fds`.
+    pub fn poll_recv_with_fds(
+        &self,
+        lw: &Waker,
+        buf: &mut [u8],
+        fds: &mut Vec<RawFd>,
+    ) -> Poll<io::Result<usize>> {
+        let (_, tick) = ready!(self.io.poll_read_ready_tick(lw)?);
+
+        let r = ancillary::recv_fds(self.io.get_ref(), buf, fds);
+
+        if is_wouldblock(&r) {
+            self.io.clear_read_ready(tick, lw)?;
+            Poll::Pending
+        } else {
+            Poll::Ready(r)
+        }
+    }
+
+    /// Receives into `buf`, appending any file descriptors sent alongside
+    /// it as `SCM_RIGHTS` ancillary data to `fds`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let stream = await!(UnixStream::connect("/tmp/sock"))?;
+    /// let mut buf = vec![0; 1024];
+    /// let mut fds = Vec::new();
+    /// let n = await!(stream.recv_with_fds(&mut buf, &mut fds))?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_with_fds(&self, buf: &mut [u8], fds: &mut Vec<RawFd>) -> io::Result<usize> {
+        loop {
+            let (_, tick) = await!(self.io.read_ready())?;
+
+            let r = ancillary::recv_fds(self.io.get_ref(), buf, fds);
+            if is_wouldblock(&r) {
+                self.io.reset_read_ready(tick);
+            } else {
+                return r;
+            }
+        }
+    }
+
+    /// Splits this stream into owned read and write halves that can be
+    /// moved into separate tasks.
+    ///
+    /// Unlike `futures::io::AsyncReadExt::split`, this does not require
+    /// the halves to share a lock: both simply hold an `Arc` around the
+    /// same underlying socket.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let stream = UnixStream::pair()?.0;
+    /// let (read_half, write_half) = stream.into_split();
+    /// # Ok(()) }
+    /// ```
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let inner = Arc::new(self);
+
+        (
+            OwnedReadHalf {
+                inner: inner.clone(),
+            },
+            OwnedWriteHalf { inner },
+        )
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().local_addr()
+    }
+
+    /// Returns the socket address of the remote half of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().peer_addr()
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.io.get_ref().take_error()
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.io.get_ref().shutdown(how)
+    }
+
+    /// Returns the credentials (PID, UID, GID) of the process on the
+    /// other end of this connection.
+    ///
+    /// On Linux this reads `SO_PEERCRED`; on macOS and the BSDs it uses
+    /// `getpeereid`, which cannot report the peer's PID.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use romio::uds::UnixStream;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let (a, _b) = UnixStream::pair()?;
+    /// let cred = a.peer_cred()?;
+    /// println!("uid: {}", cred.uid());
+    /// # Ok(()) }
+    /// ```
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        ucred::get_peer_cred(self)
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(self: Pin<&mut Self>, lw: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        UnixStream::poll_read(&self, lw, buf)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(self: Pin<&mut Self>, lw: &Waker, buf: &[u8]) -> Poll<io::Result<usize>> {
+        UnixStream::poll_write(&self, lw, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _lw: &Waker) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _lw: &Waker) -> Poll<io::Result<()>> {
+        Poll::Ready(self.io.get_ref().shutdown(Shutdown::Write))
+    }
+}
+
+impl fmt::Debug for UnixStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.io.get_ref().fmt(f)
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
+        UnixStream::from_std(net::UnixStream::from_raw_fd(fd))
+            .expect("failed to register adopted fd with the reactor")
+    }
+}
+
+impl IntoRawFd for UnixStream {
+    fn into_raw_fd(self) -> RawFd {
+        self.io
+            .into_inner()
+            .expect("failed to deregister socket from the reactor")
+            .into_raw_fd()
+    }
+}
+
+fn is_wouldblock<T>(r: &io::Result<T>) -> bool {
+    match *r {
+        Ok(_) => false,
+        Err(ref e) => e.kind() == io::ErrorKind::WouldBlock,
+    }
+}
+
+/// Owned read half of a [`UnixStream`], created by [`UnixStream::into_split`].
+///
+/// [`UnixStream`]: struct.UnixStream.html
+/// [`UnixStream::into_split`]: struct.UnixStream.html#method.into_split
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    inner: Arc<UnixStream>,
+}
+
+/// Owned write half of a [`UnixStream`], created by [`UnixStream::into_split`].
+///
+/// [`UnixStream`]: struct.UnixStream.html
+/// [`UnixStream::into_split`]: struct.UnixStream.html#method.into_split
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    inner: Arc<UnixStream>,
+}
+
+/// Error returned by [`reunite`](fn@reunite) when the two halves do not
+/// originate from the same `UnixStream`.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tried to reunite two halves that are not from the same socket"
+        )
+    }
+}
+
+impl std::error::Error for ReuniteError {}
+
+fn reunite(read: OwnedReadHalf, write: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+    if Arc::ptr_eq(&read.inner, &write.inner) {
+        drop(write);
+        // Only one strong reference is left (the one in `read`), so this
+        // always succeeds.
+        Ok(Arc::try_unwrap(read.inner).expect("UnixStream: more than one strong reference"))
+    } else {
+        Err(ReuniteError(read, write))
+    }
+}
+
+impl OwnedReadHalf {
+    /// Attempts to put the two halves of a `UnixStream` back together and
+    /// recover the original socket. Succeeds only if the two halves
+    /// originated from the same call to [`UnixStream::into_split`].
+    ///
+    /// [`UnixStream::into_split`]: struct.UnixStream.html#method.into_split
+    pub fn reunite(self, other: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+        reunite(self, other)
+    }
+
+    /// Test whether this half is ready to be read or not.
+    pub fn poll_read_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
+        self.inner.poll_read_ready(lw)
+    }
+}
+
+impl OwnedWriteHalf {
+    /// Attempts to put the two halves of a `UnixStream` back together and
+    /// recover the original socket. Succeeds only if the two halves
+    /// originated from the same call to [`UnixStream::into_split`].
+    ///
+    /// [`UnixStream::into_split`]: struct.UnixStream.html#method.into_split
+    pub fn reunite(self, other: OwnedReadHalf) -> Result<UnixStream, ReuniteError> {
+        reunite(other, self)
+    }
+
+    /// Test whether this half is ready to be written to or not.
+    pub fn poll_write_ready(&self, lw: &Waker) -> Poll<io::Result<Ready>> {
+        self.inner.poll_write_ready(lw)
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(self: Pin<&mut Self>, lw: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.inner.poll_read(lw, buf)
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(self: Pin<&mut Self>, lw: &Waker, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.inner.poll_write(lw, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _lw: &Waker) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _lw: &Waker) -> Poll<io::Result<()>> {
+        Poll::Ready(self.inner.shutdown(Shutdown::Write))
+    }
+}
+
+impl AsRawFd for OwnedReadHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsRawFd for OwnedWriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
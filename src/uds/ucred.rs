@@ -0,0 +1,118 @@
+//! Unix credentials of the remote end of a connected Unix socket.
+
+use std::io;
+
+/// Credentials of the process on the other end of a Unix socket.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct UCred {
+    /// The effective UID of the connecting process.
+    uid: u32,
+
+    /// The effective GID of the connecting process.
+    gid: u32,
+
+    /// The PID of the connecting process, when the platform exposes it.
+    pid: Option<i32>,
+}
+
+impl UCred {
+    /// Returns the PID of the process that connected, if the platform
+    /// supports retrieving it.
+    pub fn pid(&self) -> Option<i32> {
+        self.pid
+    }
+
+    /// Returns the effective UID of the process that connected.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the effective GID of the process that connected.
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use self::impl_linux::get_peer_cred;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub(crate) use self::impl_bsd::get_peer_cred;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod impl_linux {
+    use super::UCred;
+
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    pub(crate) fn get_peer_cred<T: AsRawFd>(sock: &T) -> io::Result<UCred> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut cred_size = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+        let ret = unsafe {
+            libc::getsockopt(
+                sock.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut cred_size,
+            )
+        };
+
+        if ret == 0 {
+            Ok(UCred {
+                uid: cred.uid,
+                gid: cred.gid,
+                pid: Some(cred.pid),
+            })
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+mod impl_bsd {
+    use super::UCred;
+
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    pub(crate) fn get_peer_cred<T: AsRawFd>(sock: &T) -> io::Result<UCred> {
+        let mut uid = 0;
+        let mut gid = 0;
+
+        let ret = unsafe { libc::getpeereid(sock.as_raw_fd(), &mut uid, &mut gid) };
+
+        if ret == 0 {
+            Ok(UCred {
+                uid,
+                gid,
+                // `getpeereid` has no way to report the peer's PID.
+                pid: None,
+            })
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
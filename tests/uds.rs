@@ -1,6 +1,7 @@
 #![cfg(any(unix, macos))]
 #![feature(async_await, await_macro, futures_api)]
 use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
 use std::os::unix::net::UnixStream as StdStream;
 use std::thread;
 
@@ -12,7 +13,7 @@ use futures::{Stream, StreamExt, Poll, executor};
 use log::{error, info};
 use tempdir::TempDir;
 
-use romio::uds::{UnixListener, UnixStream};
+use romio::uds::{UnixDatagram, UnixListener, UnixStream};
 
 type Error = Box<dyn std::error::Error + 'static>;
 
@@ -105,6 +106,208 @@ fn both_sides_async_using_threadpool() -> Result<(), Error>{
     Ok(())
 }
 
+#[test]
+fn cloned_listener_handles_concurrent_accepts() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let tmp_dir = TempDir::new("cloned_listener_accepts")?;
+    let file_path = tmp_dir.path().join("sock");
+
+    let listener = UnixListener::bind(&file_path)?;
+    let file_path = listener.local_addr()?;
+
+    const WORKERS: usize = 4;
+
+    // Each worker polls `accept()` on its own clone of the same listener,
+    // so they all share one `ScheduledIo` and race to register/be woken
+    // from its read waiter list.
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let listener = listener.clone();
+            thread::spawn(move || {
+                executor::block_on(async {
+                    let (mut stream, _addr) = await!(listener.accept()).unwrap();
+                    let mut buf = vec![0; THE_WINTERS_TALE.len()];
+                    await!(stream.read_exact(&mut buf)).unwrap();
+                    buf
+                })
+            })
+        })
+        .collect();
+
+    let file_path = file_path.as_pathname().unwrap().to_owned();
+    let clients: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let file_path = file_path.clone();
+            thread::spawn(move || {
+                let mut client = StdStream::connect(&file_path).unwrap();
+                client.write_all(THE_WINTERS_TALE).unwrap();
+            })
+        })
+        .collect();
+
+    for client in clients {
+        client.join().unwrap();
+    }
+
+    for worker in workers {
+        let buf = worker.join().unwrap();
+        assert_eq!(buf, THE_WINTERS_TALE);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn send_recv_fds_round_trip() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let (mut server, mut client) = UnixStream::pair()?;
+
+    let tmp_dir = TempDir::new("send_recv_fds")?;
+    let file_path = tmp_dir.path().join("payload");
+    std::fs::write(&file_path, b"fd payload")?;
+    let file = std::fs::File::open(&file_path)?;
+
+    thread::spawn(move || {
+        executor::block_on(client.send_with_fds(b"fd incoming", &[file.as_raw_fd()])).unwrap();
+    });
+
+    let (n, buf, fds) = executor::block_on(async {
+        let mut buf = vec![0; 32];
+        let mut fds = Vec::new();
+        let n = await!(server.recv_with_fds(&mut buf, &mut fds)).unwrap();
+        (n, buf, fds)
+    });
+
+    assert_eq!(&buf[..n], b"fd incoming");
+    assert_eq!(fds.len(), 1);
+
+    // The received descriptor is a separate, kernel-duplicated fd, not
+    // just the sender's fd number reinterpreted in this process; reading
+    // through it should still see the same file contents.
+    let mut received = unsafe { std::fs::File::from_raw_fd(fds[0]) };
+    let mut contents = String::new();
+    received.read_to_string(&mut contents)?;
+    assert_eq!(contents, "fd payload");
+
+    Ok(())
+}
+
+#[test]
+fn split_reunite_round_trip() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let (server, mut client) = UnixStream::pair()?;
+
+    let (mut read_half, mut write_half) = server.into_split();
+
+    // Move the reader into one task and the writer into another, so the
+    // two halves genuinely do I/O concurrently from separate tasks, then
+    // reunite only once both are done.
+    let reader = thread::spawn(move || {
+        let mut buf = vec![0; THE_WINTERS_TALE.len()];
+        executor::block_on(read_half.read_exact(&mut buf)).unwrap();
+        assert_eq!(buf, THE_WINTERS_TALE);
+        read_half
+    });
+
+    let writer = thread::spawn(move || {
+        executor::block_on(write_half.write_all(THE_WINTERS_TALE)).unwrap();
+        write_half
+    });
+
+    // The client writes first (unblocking `reader`) and then reads back
+    // what `writer` sent it.
+    thread::spawn(move || {
+        executor::block_on(client.write_all(THE_WINTERS_TALE)).unwrap();
+
+        let mut buf = vec![0; THE_WINTERS_TALE.len()];
+        executor::block_on(client.read_exact(&mut buf)).unwrap();
+        assert_eq!(buf, THE_WINTERS_TALE);
+    })
+    .join()
+    .unwrap();
+
+    let read_half = reader.join().unwrap();
+    let write_half = writer.join().unwrap();
+
+    read_half
+        .reunite(write_half)
+        .expect("reunite should succeed for a matching pair");
+
+    Ok(())
+}
+
+#[test]
+fn reunite_rejects_halves_from_different_streams() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let (a, _a_peer) = UnixStream::pair()?;
+    let (b, _b_peer) = UnixStream::pair()?;
+
+    let (a_read, _a_write) = a.into_split();
+    let (_b_read, b_write) = b.into_split();
+
+    assert!(a_read.reunite(b_write).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn peer_cred_reports_this_process() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let (a, _b) = UnixStream::pair()?;
+
+    let cred = a.peer_cred()?;
+
+    // `UnixStream::pair` connects two sockets within this same process, so
+    // the peer's credentials are just our own.
+    assert_eq!(cred.uid(), unsafe { libc::getuid() });
+    assert_eq!(cred.gid(), unsafe { libc::getgid() });
+
+    Ok(())
+}
+
+#[test]
+fn datagram_connect_send_recv_round_trip() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let tmp_dir = TempDir::new("datagram_connect_send_recv")?;
+    let server_path = tmp_dir.path().join("server.sock");
+
+    let server = UnixDatagram::bind(&server_path)?;
+    let client = UnixDatagram::connect(&server_path)?;
+
+    executor::block_on(async {
+        await!(client.send(THE_WINTERS_TALE)).unwrap();
+
+        // `server` was only bound, not connected to a specific peer, so it
+        // must receive via `recv_from` rather than `recv`.
+        let mut buf = vec![0; THE_WINTERS_TALE.len()];
+        let (n, _from) = await!(server.recv_from(&mut buf)).unwrap();
+        assert_eq!(&buf[..n], THE_WINTERS_TALE);
+    });
+
+    Ok(())
+}
+
+#[test]
+fn into_raw_fd_from_raw_fd_round_trip() -> Result<(), Error> {
+    drop(env_logger::try_init());
+    let (server, mut client) = UnixStream::pair()?;
+
+    // The descriptor must survive being deregistered from the reactor and
+    // handed back out, then adopted again, with I/O still working.
+    let fd = server.into_raw_fd();
+    let mut server = unsafe { UnixStream::from_raw_fd(fd) };
+
+    executor::block_on(async {
+        await!(client.write_all(THE_WINTERS_TALE)).unwrap();
+
+        let mut buf = vec![0; THE_WINTERS_TALE.len()];
+        await!(server.read_exact(&mut buf)).unwrap();
+        assert_eq!(buf, THE_WINTERS_TALE);
+    });
+
+    Ok(())
+}
+
 #[test]
 fn pair() -> Result<(), Error> {
     drop(env_logger::try_init());